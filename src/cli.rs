@@ -0,0 +1,70 @@
+use clap::Parser;
+
+use crate::context::PubSubConfig;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "A lightweight Solana RPC/PubSub bridge")]
+pub struct Args {
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    pub rpc_addr: String,
+
+    #[arg(long, default_value = "ws://127.0.0.1:8900")]
+    pub ws_addr: String,
+
+    #[arg(long, default_value_t = 1)]
+    pub tx_batch_size: usize,
+
+    #[arg(long, default_value = "[::]:8891")]
+    pub lite_rpc_ws_addr: String,
+
+    #[arg(long, default_value = "[::]:8890")]
+    pub lite_rpc_http_addr: String,
+
+    #[arg(long, default_value_t = 1)]
+    pub tx_batch_interval_ms: u64,
+
+    #[arg(long, default_value_t = 1_000)]
+    pub clean_interval_ms: u64,
+
+    #[arg(long, default_value_t = 16)]
+    pub fanout_size: u64,
+
+    /// Maximum number of concurrently active pubsub subscriptions.
+    #[arg(long, default_value_t = 1_000_000)]
+    pub max_active_subscriptions: usize,
+
+    /// Maximum number of non-final notifications admitted per subscription
+    /// within a 5-second window; delivery to the client is not tracked.
+    #[arg(long, default_value_t = 100_000)]
+    pub queue_capacity_items: usize,
+
+    /// Maximum combined size, in bytes, of the non-final notifications
+    /// admitted per subscription within a 5-second window.
+    #[arg(long, default_value_t = 256 * 1024 * 1024)]
+    pub queue_capacity_bytes: usize,
+
+    /// Worker threads used to serialize and send notifications. Defaults to the CPU count.
+    #[arg(long)]
+    pub notification_threads: Option<usize>,
+
+    /// Enable the (comparatively expensive) blockSubscribe endpoint.
+    #[arg(long, default_value_t = false)]
+    pub enable_block_subscription: bool,
+
+    /// Worker threads dedicated to fetching blocks for blockSubscribe.
+    #[arg(long, default_value_t = 2)]
+    pub block_fetch_threads: usize,
+}
+
+impl Args {
+    pub fn pubsub_config(&self) -> PubSubConfig {
+        PubSubConfig {
+            max_active_subscriptions: self.max_active_subscriptions,
+            queue_capacity_items: self.queue_capacity_items,
+            queue_capacity_bytes: self.queue_capacity_bytes,
+            notification_threads: self.notification_threads.unwrap_or_else(num_cpus::get),
+            enable_block_subscription: self.enable_block_subscription,
+            block_fetch_threads: self.block_fetch_threads,
+        }
+    }
+}
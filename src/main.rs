@@ -16,6 +16,8 @@ pub async fn main() -> anyhow::Result<()> {
         ColorChoice::Auto,
     )?;
 
+    let args = Args::parse();
+    let pubsub_config = args.pubsub_config();
     let Args {
         rpc_addr,
         ws_addr,
@@ -25,13 +27,19 @@ pub async fn main() -> anyhow::Result<()> {
         tx_batch_interval_ms,
         clean_interval_ms,
         fanout_size,
-    } = Args::parse();
+        ..
+    } = args;
 
     let tx_batch_interval_ms = Duration::from_millis(tx_batch_interval_ms);
     let clean_interval_ms = Duration::from_millis(clean_interval_ms);
 
-    let light_bridge =
-        LiteBridge::new(Url::from_str(&rpc_addr).unwrap(), &ws_addr, fanout_size).await?;
+    let light_bridge = LiteBridge::new(
+        Url::from_str(&rpc_addr).unwrap(),
+        &ws_addr,
+        fanout_size,
+        pubsub_config,
+    )
+    .await?;
 
     let services = light_bridge
         .start_services(